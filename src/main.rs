@@ -4,6 +4,8 @@ extern crate prettytable;
 use anyhow::{anyhow, bail, Result};
 use clap::{AppSettings, Clap};
 use prettytable::Table;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 use yahoo_finance::history;
 use z3::ast::{self, Real};
@@ -18,94 +20,220 @@ struct Opts {
     #[clap(short, long)]
     download_current_prices: bool,
     #[clap(short, long)]
-    target_buy: Option<f64>,
+    target_buy: Option<Decimal>,
+    #[clap(long)]
+    allow_sell: bool,
+    /// Project the resulting portfolio's value this many trading days forward
+    /// via Monte Carlo simulation.
+    #[clap(long)]
+    simulate: Option<u32>,
+    /// Split the purchase into this many sequential dollar-cost-averaging
+    /// tranches instead of solving once for the full budget.
+    #[clap(long)]
+    tranches: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct Fund {
-    shares: f64,
+    shares: Decimal,
     #[serde(default)]
-    price: f64,
+    price: Decimal,
     symbol: String,
-    target_proportion: f64,
+    target_proportion: Decimal,
+    // Absent for single-currency portfolios, in which case the fund is
+    // assumed to already be priced in `Config::base_currency`.
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    commission_fixed: Option<Decimal>,
+    #[serde(default)]
+    commission_pct: Option<Decimal>,
+    #[serde(default)]
+    min_trade_volume: Option<Decimal>,
+    // `price` converted into the portfolio's base currency; populated by
+    // `Config::convert_to_base_currency` before the model is built, since
+    // every amount the optimizer reasons about must share one currency.
+    #[serde(skip)]
+    base_price: Decimal,
+    // `currency`, resolved to `Config::base_currency` when unset; populated
+    // alongside `base_price`.
+    #[serde(skip)]
+    resolved_currency: String,
+}
+
+fn default_base_currency() -> String {
+    "USD".to_string()
 }
 
 #[derive(Deserialize)]
 struct Config {
-    target_buy: f64,
+    target_buy: Decimal,
     funds: Vec<Fund>,
+    #[serde(default = "default_base_currency")]
+    base_currency: String,
+    #[serde(default)]
+    commission_fixed: Option<Decimal>,
+    #[serde(default)]
+    commission_pct: Option<Decimal>,
+    #[serde(default)]
+    min_trade_volume: Option<Decimal>,
+    #[serde(default)]
+    allow_sell: bool,
 }
 
 impl Config {
     fn validate(&self) -> Result<()> {
-        let fund_proportion_sum: f64 = self.funds.iter().map(|f| f.target_proportion).sum();
-        if (fund_proportion_sum - 1.0).abs() > 0.01 {
+        let fund_proportion_sum: Decimal = self.funds.iter().map(|f| f.target_proportion).sum();
+        if (fund_proportion_sum - Decimal::ONE).abs() > Decimal::new(1, 2) {
             bail!(
                 "expected target_proportions to sum to 1.00, got {:}",
                 fund_proportion_sum
             );
         }
         for f in self.funds.iter() {
-            if f.price.is_sign_negative() || f.price == 0f64 {
+            if f.price.is_sign_negative() || f.price == Decimal::ZERO {
                 bail!("price for {} is not positive", f.symbol);
             }
         }
         Ok(())
     }
+
+    // Funds inherit the portfolio-wide commission/min-trade-volume defaults
+    // unless they specify their own, so per-fund overrides only need to be
+    // set where a broker's fee schedule actually differs.
+    fn apply_trade_cost_defaults(&mut self) {
+        let commission_fixed = self.commission_fixed;
+        let commission_pct = self.commission_pct;
+        let min_trade_volume = self.min_trade_volume;
+        for f in self.funds.iter_mut() {
+            f.commission_fixed = f.commission_fixed.or(commission_fixed);
+            f.commission_pct = f.commission_pct.or(commission_pct);
+            f.min_trade_volume = f.min_trade_volume.or(min_trade_volume);
+        }
+    }
+
+    // Populates each fund's `base_price` so the optimizer can reason about a
+    // single portfolio-wide currency, even when funds are quoted in several.
+    async fn convert_to_base_currency(&mut self) -> Result<()> {
+        for f in self.funds.iter_mut() {
+            let currency = f
+                .currency
+                .clone()
+                .unwrap_or_else(|| self.base_currency.clone());
+            let rate = fx_rate(&currency, &self.base_currency).await?;
+            f.base_price = f.price * rate;
+            f.resolved_currency = currency;
+        }
+        Ok(())
+    }
 }
 
-fn f64_to_real(ctx: &Context, val: f64) -> Real {
-    // NOTE: This is lossy, since we only use 3 decimal digits.
-    ast::Real::from_real_str(ctx, &format!("{:.3}", val), "1").unwrap()
+fn decimal_to_real(ctx: &Context, val: Decimal) -> Real {
+    // Build the Real from the Decimal's own unscaled numerator/denominator
+    // pair, rather than rounding through a fixed number of decimal digits,
+    // so the model sees exactly the value the user (or the config file)
+    // specified.
+    let denominator = 10i128.pow(val.scale());
+    ast::Real::from_real_str(ctx, &val.mantissa().to_string(), &denominator.to_string()).unwrap()
 }
 
-fn construct_model<'a>(ctx: &'a Context, funds: &Vec<Fund>, target_buy: f64) -> Option<Model<'a>> {
+fn construct_model<'a>(
+    ctx: &'a Context,
+    funds: &Vec<Fund>,
+    target_buy: Decimal,
+    allow_sell: bool,
+) -> Option<Model<'a>> {
     let optimize = z3::Optimize::new(&ctx);
 
+    let zero = ast::Real::from_real(&ctx, 0, 1);
+    let zero_int = ast::Int::from_i64(&ctx, 0);
+
     let mut vars = Vec::new();
     let mut total_bought = ast::Real::from_real(&ctx, 0, 1);
     let mut total_existing = ast::Real::from_real(&ctx, 0, 1);
+    let mut total_commission = ast::Real::from_real(&ctx, 0, 1);
     for f in funds.iter() {
         let v = ast::Int::new_const(&ctx, f.symbol.clone());
-        optimize.assert(&v.ge(&ast::Int::from_i64(&ctx, 0)));
-        let price = f64_to_real(&ctx, f.price);
+        if allow_sell {
+            // Can't sell more shares than are actually held. Floor to the
+            // largest whole share count actually sellable: a holder of 10.9
+            // shares can only sell 10. `to_i64` only fails on overflow, which
+            // no realistic share count should hit, so fall back to an
+            // effectively unbounded floor rather than silently capping sales
+            // at zero.
+            let shares_held = f.shares.floor().to_i64().unwrap_or(i64::MAX);
+            optimize.assert(&(&v + ast::Int::from_i64(&ctx, shares_held)).ge(&zero_int));
+        } else {
+            optimize.assert(&v.ge(&zero_int));
+        }
+        let price = decimal_to_real(&ctx, f.base_price);
+
+        // |price * v|, used both to size the percentage commission and to
+        // enforce `min_trade_volume` — a sell must cost the same fee a buy of
+        // the same size would, not earn a rebate.
+        let trade_volume = &price * ast::Real::from_int(&v);
+        let trade_magnitude = trade_volume
+            .clone()
+            .lt(&zero)
+            .ite(&(-trade_volume.clone()), &trade_volume);
+
+        // A fund only incurs a commission when it's actually traded, so tie a
+        // boolean "trade happens" variable to the share count rather than
+        // charging the fee unconditionally.
+        let traded = ast::Bool::new_const(&ctx, format!("{}_traded", f.symbol));
+        optimize.assert(&v.gt(&zero_int).implies(&traded));
+        optimize.assert(&v.lt(&zero_int).implies(&traded));
+        let commission = traded.ite(
+            &(decimal_to_real(&ctx, f.commission_fixed.unwrap_or(Decimal::ZERO))
+                + decimal_to_real(&ctx, f.commission_pct.unwrap_or(Decimal::ZERO)) * &trade_magnitude),
+            &zero,
+        );
+        total_commission += &commission;
+
+        if let Some(min_trade_volume) = f.min_trade_volume {
+            optimize.assert(
+                &v._eq(&zero_int)
+                    .or(&[&trade_magnitude.ge(&decimal_to_real(&ctx, min_trade_volume))]),
+            );
+        }
+
         total_bought += ast::Real::from_int(&v) * &price;
-        total_existing += f64_to_real(&ctx, f.shares) * &price;
+        total_existing += decimal_to_real(&ctx, f.shares) * &price;
         vars.push((f, v));
     }
     let new_total = &total_bought + &total_existing;
 
-    let mut objective = ast::Real::from_real(&ctx, 0, 1);
+    let mut objective = total_commission.clone();
     for f in funds.iter() {
         let v = ast::Int::new_const(&ctx, f.symbol.clone());
-        let price = f64_to_real(&ctx, f.price);
-        let delta_from_ideal = (price * (ast::Real::from_int(&v) + f64_to_real(&ctx, f.shares)))
-            - (&new_total * &f64_to_real(&ctx, f.target_proportion));
+        let price = decimal_to_real(&ctx, f.base_price);
+        let delta_from_ideal = (price
+            * (ast::Real::from_int(&v) + decimal_to_real(&ctx, f.shares)))
+            - (&new_total * &decimal_to_real(&ctx, f.target_proportion));
         objective += delta_from_ideal
             .clone()
             .lt(&ast::Real::from_real(&ctx, 0, 1))
             .ite(&(-delta_from_ideal.clone()), &delta_from_ideal.clone());
     }
 
-    let target_buy = &f64_to_real(&ctx, target_buy);
-    optimize.assert(&total_bought.lt(&target_buy));
+    // With `allow_sell`, `target_buy` is a net cash flow rather than a pure
+    // spend: sells contribute negative amounts to `total_bought`, so this
+    // constraint caps net deposits while still letting a sell-funded
+    // rebalance target zero (or a net withdrawal).
+    let target_buy = &decimal_to_real(&ctx, target_buy);
+    optimize.assert(&(&total_bought + &total_commission).lt(&target_buy));
 
     // Add penalty for going below the target amount
-    objective += (target_buy - total_bought) * f64_to_real(&ctx, 1.0);
+    objective += (target_buy - total_bought) * ast::Real::from_real(&ctx, 1, 1);
     optimize.minimize(&objective);
 
     optimize.check(&[]);
-    optimize.get_model().map(|model| Model {
-        ctx,
-        model,
-        new_total,
-    })
+    optimize.get_model().map(|model| Model { ctx, model })
 }
 
 struct Model<'a> {
     ctx: &'a z3::Context,
     model: z3::Model<'a>,
-    new_total: z3::ast::Real<'a>,
 }
 
 impl<'a> Model<'a> {
@@ -114,22 +242,35 @@ impl<'a> Model<'a> {
             .eval(&ast::Int::new_const(self.ctx, fund.symbol.clone()))
             .and_then(|s| s.as_i64())
     }
+}
 
-    fn new_proportion(&self, fund: &Fund) -> Option<f64> {
-        match self.optimal_shares(&fund) {
-            Some(shares) => self
-                .new_portfolio_total()
-                .map(|total| ((shares as f64) + fund.shares) * fund.price / total),
-            None => None,
-        }
+// Evaluating the portfolio total/proportions through z3's `Real::as_real()`
+// round-trips through an i64 numerator/denominator, which overflows (and
+// silently returns `None`) once `decimal_to_real`'s exact conversion is
+// feeding it high-scale Decimals (e.g. FX-converted or downloaded prices).
+// Recomputing directly from the solved share counts and `Decimal` prices
+// keeps the reporting as exact as the model itself.
+fn portfolio_total(model: &Model, funds: &[Fund]) -> Result<Decimal> {
+    let mut total = Decimal::ZERO;
+    for f in funds {
+        let shares = model
+            .optimal_shares(f)
+            .ok_or_else(|| anyhow!("failed to evaluate {}", f.symbol))?;
+        total += f.base_price * (f.shares + Decimal::from(shares));
     }
+    Ok(total)
+}
 
-    fn new_portfolio_total(&self) -> Option<f64> {
-        self.model
-            .eval(&self.new_total)
-            .and_then(|total| total.as_real())
-            .map(|(num, dem)| (num as f64) / (dem as f64))
-    }
+fn fund_new_proportion(model: &Model, fund: &Fund, portfolio_total: Decimal) -> Result<Decimal> {
+    let shares = model
+        .optimal_shares(fund)
+        .ok_or_else(|| anyhow!("failed to evaluate {}", fund.symbol))?;
+    let value = fund.base_price * (fund.shares + Decimal::from(shares));
+    Ok(if portfolio_total == Decimal::ZERO {
+        Decimal::ZERO
+    } else {
+        value / portfolio_total
+    })
 }
 
 async fn fund_price(symbol: &str) -> Result<f64> {
@@ -140,6 +281,276 @@ async fn fund_price(symbol: &str) -> Result<f64> {
     }
 }
 
+// Fetches the spot rate for converting an amount in `from` into `to`,
+// reusing the same Yahoo Finance history endpoint fund prices come from
+// (FX pairs are quoted there as e.g. "EURUSD=X").
+async fn fx_rate(from: &str, to: &str) -> Result<Decimal> {
+    if from == to {
+        return Ok(Decimal::ONE);
+    }
+    Ok(Decimal::try_from(
+        fund_price(&format!("{}{}=X", from, to)).await?,
+    )?)
+}
+
+const SIMULATION_PATHS: u32 = 10_000;
+
+// Minimal PCG32 (XSH-RR) generator, so the Monte Carlo simulation is
+// reproducible without pulling in a dependency just for randomness.
+struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    fn new(seed: u64, sequence: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (sequence << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        (xorshifted >> rot) | (xorshifted << ((32u32.wrapping_sub(rot)) & 31))
+    }
+
+    // Uniform in (0, 1), so it's safe to feed directly into `ln`.
+    fn next_uniform(&mut self) -> f64 {
+        ((self.next_u32() as f64) + 1.0) / (u32::MAX as f64 + 2.0)
+    }
+}
+
+fn standard_normal(rng: &mut Pcg32) -> f64 {
+    let u1 = rng.next_uniform();
+    let u2 = rng.next_uniform();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+// Estimates the daily log-return mean and standard deviation for a fund from
+// its historical closing prices, for use as the drift/volatility of a
+// geometric Brownian motion path.
+async fn fund_return_stats(symbol: &str) -> Result<(f64, f64)> {
+    let history = history::retrieve_interval(symbol, yahoo_finance::Interval::_1d).await?;
+    let closes: Vec<f64> = history.iter().map(|bar| bar.close).collect();
+    if closes.len() < 2 {
+        bail!("not enough history to estimate return statistics for {}", symbol);
+    }
+    let log_returns: Vec<f64> = closes
+        .windows(2)
+        .map(|w| (w[1] / w[0]).ln())
+        .collect();
+    let mu = log_returns.iter().sum::<f64>() / log_returns.len() as f64;
+    let variance = log_returns.iter().map(|r| (r - mu).powi(2)).sum::<f64>() / log_returns.len() as f64;
+    Ok((mu, variance.sqrt()))
+}
+
+struct SimulationSummary {
+    p5: f64,
+    p50: f64,
+    p95: f64,
+    prob_loss: f64,
+}
+
+// Projects each fund independently forward `days` trading days via geometric
+// Brownian motion (`S <- S * exp((mu - sigma^2/2)*dt + sigma*sqrt(dt)*Z)`),
+// sums the per-fund ending values into a total portfolio value per path, and
+// summarizes the resulting distribution.
+fn simulate_portfolio(
+    funds: &[(f64, f64, f64, f64)], // (base price, shares, mu, sigma)
+    days: u32,
+    paths: u32,
+    seed: u64,
+) -> SimulationSummary {
+    let dt = 1.0_f64;
+    let initial_total: f64 = funds.iter().map(|&(price, shares, _, _)| price * shares).sum();
+
+    let mut totals: Vec<f64> = (0..paths)
+        .map(|path| {
+            let mut rng = Pcg32::new(seed, path as u64);
+            funds
+                .iter()
+                .map(|&(price, shares, mu, sigma)| {
+                    let mut s = price;
+                    for _ in 0..days {
+                        let z = standard_normal(&mut rng);
+                        s *= ((mu - sigma * sigma / 2.0) * dt + sigma * dt.sqrt() * z).exp();
+                    }
+                    s * shares
+                })
+                .sum()
+        })
+        .collect();
+    totals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let percentile = |p: f64| totals[((p * (totals.len() - 1) as f64).round() as usize)];
+    let prob_loss =
+        totals.iter().filter(|&&t| t < initial_total).count() as f64 / totals.len() as f64;
+
+    SimulationSummary {
+        p5: percentile(0.05),
+        p50: percentile(0.5),
+        p95: percentile(0.95),
+        prob_loss,
+    }
+}
+
+// Prints the per-fund purchase/sale table for one solved `model` and returns
+// the total amount spent and the resulting portfolio total (both in the base
+// currency).
+fn print_purchase_table(
+    funds: &[Fund],
+    model: &Model,
+    base_currency: &str,
+) -> Result<(f64, Decimal)> {
+    let portfolio_total = portfolio_total(model, funds)?;
+    let mut table = Table::new();
+    table.add_row(row![
+        b->"Fund", b->"Action", b->"Shares", b->"Native Amt", b->format!("Amt ({})", base_currency), b->"New Proportion"
+    ]);
+    let mut total = 0.0;
+    for f in funds {
+        let shares = model
+            .optimal_shares(f)
+            .ok_or(anyhow!("failed to evaluate {}", f.symbol))?;
+        let action = match shares {
+            s if s > 0 => "Buy",
+            s if s < 0 => "Sell",
+            _ => "Hold",
+        };
+        let native_purchase = f.price.to_f64().unwrap_or(0.0) * (shares as f64);
+        let purchase = f.base_price.to_f64().unwrap_or(0.0) * (shares as f64);
+        total += purchase;
+        let new_proportion = fund_new_proportion(model, f, portfolio_total)?;
+        table.add_row(row![
+            bc->f.symbol,
+            c->action,
+            r->shares.abs(),
+            r->format!("{:.2} {}", native_purchase.abs(), f.resolved_currency),
+            r->format!("${:.2}", purchase.abs()),
+            r->format!("{:.2}%", new_proportion.to_f64().unwrap_or(0.0) * 100.0),
+        ]);
+    }
+    table.printstd();
+    Ok((total, portfolio_total))
+}
+
+fn print_simulation_summary(summary: &SimulationSummary) {
+    println!("5th percentile:  \t${:.2}", summary.p5);
+    println!("Median:          \t${:.2}", summary.p50);
+    println!("95th percentile: \t${:.2}", summary.p95);
+    println!("Probability of loss: \t{:.1}%", summary.prob_loss * 100.0);
+}
+
+async fn run_simulation(funds: &[Fund], model: &Model<'_>, days: u32) -> Result<()> {
+    println!(
+        "\nSimulating portfolio value {} trading days out ({} paths)...",
+        days, SIMULATION_PATHS
+    );
+    let mut sim_inputs = Vec::with_capacity(funds.len());
+    for f in funds {
+        let shares = model
+            .optimal_shares(f)
+            .ok_or(anyhow!("failed to evaluate {}", f.symbol))?;
+        let (mu, sigma) = fund_return_stats(&f.symbol).await?;
+        let new_shares = (shares as f64) + f.shares.to_f64().unwrap_or(0.0);
+        sim_inputs.push((f.base_price.to_f64().unwrap_or(0.0), new_shares, mu, sigma));
+    }
+    let summary = simulate_portfolio(&sim_inputs, days, SIMULATION_PATHS, 0xcafe_f00d_d15e_a5e5);
+    print_simulation_summary(&summary);
+    Ok(())
+}
+
+// Like `run_simulation`, but for final holdings that already include any
+// purchases — e.g. after a `--tranches` plan has folded each step's shares
+// into `funds` — so there's no separate "shares bought this solve" to add.
+async fn run_simulation_for_holdings(funds: &[Fund], days: u32) -> Result<()> {
+    println!(
+        "\nSimulating portfolio value {} trading days out ({} paths)...",
+        days, SIMULATION_PATHS
+    );
+    let mut sim_inputs = Vec::with_capacity(funds.len());
+    for f in funds {
+        let (mu, sigma) = fund_return_stats(&f.symbol).await?;
+        sim_inputs.push((
+            f.base_price.to_f64().unwrap_or(0.0),
+            f.shares.to_f64().unwrap_or(0.0),
+            mu,
+            sigma,
+        ));
+    }
+    let summary = simulate_portfolio(&sim_inputs, days, SIMULATION_PATHS, 0xcafe_f00d_d15e_a5e5);
+    print_simulation_summary(&summary);
+    Ok(())
+}
+
+// Solves `tranches` sequential purchases of `target_buy / tranches` each,
+// folding every step's chosen shares into `funds` before the next solve, so
+// each step's glide path accounts for what was already bought. Prints one
+// table per tranche plus a cumulative final-allocation summary, then
+// projects the resulting holdings if `simulate` is set.
+async fn run_tranche_plan(
+    ctx: &Context,
+    funds: &mut Vec<Fund>,
+    target_buy: Decimal,
+    allow_sell: bool,
+    tranches: u32,
+    base_currency: &str,
+    simulate: Option<u32>,
+) -> Result<()> {
+    if tranches == 0 {
+        bail!("--tranches must be greater than zero");
+    }
+    let step_budget = target_buy / Decimal::from(tranches);
+    for step in 1..=tranches {
+        println!("\nTranche {}/{} (budget ${:.2}):", step, tranches, step_budget);
+        let model = construct_model(ctx, funds, step_budget, allow_sell)
+            .ok_or(anyhow!("evaluating model failed"))?;
+        print_purchase_table(funds, &model, base_currency)?;
+
+        for f in funds.iter_mut() {
+            let shares = model
+                .optimal_shares(f)
+                .ok_or(anyhow!("failed to evaluate {}", f.symbol))?;
+            f.shares += Decimal::from(shares);
+        }
+    }
+
+    println!("\nCumulative final allocation after {} tranches:", tranches);
+    let total_value: Decimal = funds.iter().map(|f| f.base_price * f.shares).sum();
+    let mut summary = Table::new();
+    summary.add_row(row![b->"Fund", b->"Shares", b->"Final Proportion"]);
+    for f in funds.iter() {
+        let value = f.base_price * f.shares;
+        let proportion = if total_value == Decimal::ZERO {
+            Decimal::ZERO
+        } else {
+            value / total_value
+        };
+        summary.add_row(row![
+            bc->f.symbol,
+            r->f.shares,
+            r->format!("{:.2}%", proportion.to_f64().unwrap_or(0.0) * 100.0),
+        ]);
+    }
+    summary.printstd();
+
+    if let Some(days) = simulate {
+        run_simulation_for_holdings(funds, days).await?;
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
@@ -150,50 +561,52 @@ async fn main() -> Result<()> {
     if opts.download_current_prices {
         println!("Downloading current fund prices...\nCurrent prices:");
         for f in config.funds.iter_mut() {
-            f.price = fund_price(&f.symbol).await?;
+            f.price = Decimal::try_from(fund_price(&f.symbol).await?)?;
             println!("{}:\t${:.2}", f.symbol, f.price);
         }
         println!("");
     }
 
+    config.apply_trade_cost_defaults();
     config.validate()?;
-    let funds = config.funds;
+    config.convert_to_base_currency().await?;
+    let mut funds = config.funds;
 
     let target_buy = match opts.target_buy {
         Some(val) => val,
         None => config.target_buy,
     };
+    let allow_sell = opts.allow_sell || config.allow_sell;
 
     let ctx = Context::new(&z3::Config::new());
-    let model =
-        construct_model(&ctx, &funds, target_buy).ok_or(anyhow!("evaluating model failed"))?;
 
-    println!("Optimal purchasing strategy:");
-    let mut table = Table::new();
-    table.add_row(row![b->"Fund", b->"Shares to Buy", b->"Buy Amt", b->"New Proportion"]);
-    let mut total = 0.0;
-    for f in funds {
-        let shares = model
-            .optimal_shares(&f)
-            .ok_or(anyhow!("failed to evaluate {}", f.symbol))?;
-        let purchase = f.price * (shares as f64);
-        total += purchase;
-        let new_proportion = model
-            .new_proportion(&f)
-            .ok_or(anyhow!("unable to get new proportion for {}", f.symbol))?;
-        table.add_row(row![
-            bc->f.symbol,
-            r->shares,
-            r->format!("${:.2}", purchase),
-            r->format!("{:.2}%", new_proportion * 100.0),
-        ]);
+    if let Some(tranches) = opts.tranches {
+        return run_tranche_plan(
+            &ctx,
+            &mut funds,
+            target_buy,
+            allow_sell,
+            tranches,
+            &config.base_currency,
+            opts.simulate,
+        )
+        .await;
     }
-    table.printstd();
+
+    let model = construct_model(&ctx, &funds, target_buy, allow_sell)
+        .ok_or(anyhow!("evaluating model failed"))?;
+
+    println!("Optimal purchasing strategy:");
+    let (total, new_portfolio_total) = print_purchase_table(&funds, &model, &config.base_currency)?;
     println!("\nTotal purchase:\t\t${:.2}", total);
     println!(
         "New portfolio total: \t${:.2}",
-        model.new_portfolio_total().unwrap()
+        new_portfolio_total.to_f64().unwrap_or(0.0)
     );
 
+    if let Some(days) = opts.simulate {
+        run_simulation(&funds, &model, days).await?;
+    }
+
     Ok(())
 }